@@ -1,38 +1,92 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use web_sys::HtmlInputElement;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{File, HtmlInputElement};
 use yew::prelude::*;
 use yew_agent::{Bridge, Bridged};
 
+use crate::markdown;
+use crate::services::call::{CallEvent, CallService, SignalPayload};
 use crate::services::event_bus::EventBus;
-use crate::{services::websocket::WebsocketService, User};
+use crate::services::upload::{self, Attachment};
+use crate::services::websocket::{MsgTypes, Status, WebSocketMessage, WebsocketService};
+use crate::User;
 
 pub enum Msg {
     HandleMsg(String),
     SubmitMessage,
+    WsStatus(Status),
+    SelectConversation(Option<String>),
+    Call(String),
+    AcceptCall,
+    DeclineCall,
+    HangUp,
+    CallEvent(CallEvent),
+    FileSelected(File),
+    AttachmentUploaded(Result<Attachment, String>),
+}
+
+/// A message body is either typed text (rendered as Markdown) or a
+/// previously-uploaded attachment, replacing the old `.gif`-suffix check.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum MessageContent {
+    Attachment(AttachmentContent),
+    Text(String),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct AttachmentContent {
+    kind: String,
+    url: String,
+    mime: String,
+    filename: String,
+}
+
+impl From<Attachment> for MessageContent {
+    fn from(a: Attachment) -> Self {
+        MessageContent::Attachment(AttachmentContent {
+            kind: "attachment".to_string(),
+            url: a.url,
+            mime: a.mime,
+            filename: a.filename,
+        })
+    }
+}
+
+/// Renders a message body: Markdown for text, or an inline preview (image,
+/// video, audio) for an attachment, falling back to a download chip for
+/// anything else.
+fn render_content(content: &MessageContent) -> Html {
+    let attachment = match content {
+        MessageContent::Text(text) => return markdown::render(text),
+        MessageContent::Attachment(attachment) => attachment,
+    };
+
+    if attachment.mime.starts_with("image/") {
+        html! { <img class="mt-2 max-w-xs rounded-lg" src={attachment.url.clone()} alt={attachment.filename.clone()}/> }
+    } else if attachment.mime.starts_with("video/") {
+        html! { <video class="mt-2 max-w-xs rounded-lg" src={attachment.url.clone()} controls=true/> }
+    } else if attachment.mime.starts_with("audio/") {
+        html! { <audio class="mt-2" src={attachment.url.clone()} controls=true/> }
+    } else {
+        html! {
+            <a class="mt-2 inline-block bg-gray-200 rounded-full px-3 py-1" href={attachment.url.clone()} target="_blank">
+                {format!("📎 {}", attachment.filename)}
+            </a>
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 struct MessageData {
     from: String,
-    message: String,
+    message: MessageContent,
     #[serde(default)]
     timestamp: Option<String>,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-pub enum MsgTypes {
-    Users,
-    Register,
-    Message,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct WebSocketMessage {
-    message_type: MsgTypes,
-    data_array: Option<Vec<String>>,
-    data: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -41,12 +95,36 @@ struct UserProfile {
     avatar: String,
 }
 
+/// Shown above the composer while an attachment is uploading, or after its
+/// upload failed.
+enum UploadStatus {
+    Uploading,
+    Failed(String),
+}
+
 pub struct Chat {
+    username: String,
     users: Vec<UserProfile>,
     chat_input: NodeRef,
+    file_input: NodeRef,
+    upload_status: Option<UploadStatus>,
     _producer: Box<dyn Bridge<EventBus>>,
     wss: WebsocketService,
     messages: Vec<MessageData>,
+    /// Private conversations keyed by the other party's username.
+    whispers: HashMap<String, Vec<MessageData>>,
+    /// Unread whisper count per peer, cleared when their conversation is opened.
+    unread: HashMap<String, usize>,
+    /// `None` is the public room; `Some(peer)` is an open whisper conversation.
+    active_conversation: Option<String>,
+    status: Status,
+    /// Envelopes submitted while disconnected, flushed once reconnected.
+    pending: Vec<WebSocketMessage>,
+    call: CallService,
+    /// Caller awaiting accept/decline, shown as a prompt.
+    incoming_call: Option<String>,
+    /// Peer of the call currently in progress, if any.
+    active_call: Option<String>,
 }
 
 impl Component for Chat {
@@ -58,33 +136,35 @@ impl Component for Chat {
             .link()
             .context::<User>(Callback::noop())
             .expect("context to be set");
-        let wss = WebsocketService::new();
         let username = user.username.borrow().clone();
-
-        let message = WebSocketMessage {
-            message_type: MsgTypes::Register,
-            data: Some(username.to_string()),
-            data_array: None,
-        };
-
-        if let Ok(_) = wss
-            .tx
-            .clone()
-            .try_send(serde_json::to_string(&message).unwrap())
-        {
-            log::debug!("message sent successfully");
-        }
+        let wss = WebsocketService::new(username.clone(), ctx.link().callback(Msg::WsStatus));
+        let call = CallService::new(
+            username.clone(),
+            wss.tx.clone(),
+            ctx.link().callback(Msg::CallEvent),
+        );
 
         Self {
+            username,
             users: vec![],
             messages: vec![],
+            whispers: HashMap::new(),
+            unread: HashMap::new(),
+            active_conversation: None,
             chat_input: NodeRef::default(),
+            file_input: NodeRef::default(),
+            upload_status: None,
             wss,
+            status: Status::Connecting,
+            pending: vec![],
+            call,
+            incoming_call: None,
+            active_call: None,
             _producer: EventBus::bridge(ctx.link().callback(Msg::HandleMsg)),
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::HandleMsg(s) => {
                 match serde_json::from_str::<WebSocketMessage>(&s) {
@@ -119,6 +199,38 @@ impl Component for Chat {
                                     }
                                 }
                             }
+                            MsgTypes::Whisper => {
+                                if let Some(data) = msg.data {
+                                    match serde_json::from_str::<MessageData>(&data) {
+                                        Ok(message_data) => {
+                                            log::debug!("Received whisper: {:?}", message_data);
+                                            let peer = if message_data.from == self.username {
+                                                message_data.to.clone().unwrap_or_default()
+                                            } else {
+                                                message_data.from.clone()
+                                            };
+                                            if self.active_conversation.as_deref() != Some(peer.as_str()) {
+                                                *self.unread.entry(peer.clone()).or_insert(0) += 1;
+                                            }
+                                            self.whispers.entry(peer).or_default().push(message_data);
+                                            return true;
+                                        }
+                                        Err(e) => {
+                                            log::error!("Error parsing whisper data: {:?}", e);
+                                        }
+                                    }
+                                }
+                            }
+                            MsgTypes::Signal => {
+                                if let Some(data) = msg.data {
+                                    match serde_json::from_str::<SignalPayload>(&data) {
+                                        Ok(payload) => self.call.handle_signal(payload),
+                                        Err(e) => {
+                                            log::error!("Error parsing signal payload: {:?}", e);
+                                        }
+                                    }
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -131,70 +243,217 @@ impl Component for Chat {
             Msg::SubmitMessage => {
                 let input = self.chat_input.cast::<HtmlInputElement>();
                 if let Some(input) = input {
-                    let js_time = js_sys::Date::new_0().to_locale_time_string("id-ID");
-                    let current_time = js_time.as_string().unwrap_or_default();
-                    
                     let message_content = input.value();
                     if !message_content.is_empty() {
-                        let message_data = serde_json::json!({
-                            "message": message_content,
-                            "timestamp": current_time
-                        });
-                        
-                        let message = WebSocketMessage {
-                            message_type: MsgTypes::Message,
-                            data: Some(serde_json::to_string(&message_data).unwrap()),
-                            data_array: None,
-                        };
-                        
-                        if let Err(e) = self
-                            .wss
-                            .tx
-                            .clone()
-                            .try_send(serde_json::to_string(&message).unwrap())
-                        {
-                            log::error!("Error sending to channel: {:?}", e);
-                        }
+                        self.send_content(MessageContent::Text(message_content));
                         input.set_value("");
                     }
                 };
                 false
             }
+            Msg::FileSelected(file) => {
+                self.upload_status = Some(UploadStatus::Uploading);
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    let result = upload::upload(file).await;
+                    link.send_message(Msg::AttachmentUploaded(result));
+                });
+                true
+            }
+            Msg::AttachmentUploaded(result) => {
+                match result {
+                    Ok(attachment) => {
+                        self.upload_status = None;
+                        self.send_content(attachment.into());
+                    }
+                    Err(e) => {
+                        log::error!("Attachment upload failed: {}", e);
+                        self.upload_status = Some(UploadStatus::Failed(e));
+                    }
+                }
+                if let Some(input) = self.file_input.cast::<HtmlInputElement>() {
+                    input.set_value("");
+                }
+                true
+            }
+            Msg::WsStatus(status) => {
+                if status == Status::Connected && self.status != Status::Connected {
+                    let mut tx = self.wss.tx.clone();
+                    for message in self.pending.drain(..) {
+                        if let Err(e) = tx.try_send(message) {
+                            log::error!("Error flushing pending message: {:?}", e);
+                        }
+                    }
+                }
+                self.status = status;
+                true
+            }
+            Msg::SelectConversation(peer) => {
+                if let Some(peer) = &peer {
+                    self.unread.remove(peer);
+                }
+                self.active_conversation = peer;
+                true
+            }
+            Msg::Call(peer) => {
+                self.call.call(peer);
+                false
+            }
+            Msg::AcceptCall => {
+                self.incoming_call = None;
+                self.call.accept_incoming();
+                false
+            }
+            Msg::DeclineCall => {
+                self.incoming_call = None;
+                self.call.decline_incoming();
+                true
+            }
+            Msg::HangUp => {
+                self.active_call = None;
+                self.call.hang_up();
+                true
+            }
+            Msg::CallEvent(event) => {
+                match event {
+                    CallEvent::Ringing { from } => self.incoming_call = Some(from),
+                    CallEvent::Connected { peer } => self.active_call = Some(peer),
+                    CallEvent::Ended => {
+                        self.incoming_call = None;
+                        self.active_call = None;
+                    }
+                }
+                true
+            }
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let submit = ctx.link().callback(|_| Msg::SubmitMessage);
 
+        let status_banner = match self.status {
+            Status::Connected => None,
+            Status::Connecting => Some(("bg-yellow-100 text-yellow-800", "Connecting…")),
+            Status::Reconnecting => Some(("bg-yellow-100 text-yellow-800", "Reconnecting…")),
+            Status::Disconnected => Some(("bg-red-100 text-red-800", "Disconnected")),
+        };
+
+        let open_public = ctx.link().callback(|_| Msg::SelectConversation(None));
+        let active_messages: &[MessageData] = match &self.active_conversation {
+            Some(peer) => self
+                .whispers
+                .get(peer)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            None => &self.messages,
+        };
+
         html! {
             <div class="flex w-screen">
                 <div class="flex-none w-56 h-screen bg-gray-100">
-                    <div class="text-xl p-3">{"Users"}</div>
+                    <div
+                        class={classes!(
+                            "text-xl", "p-3", "cursor-pointer",
+                            self.active_conversation.is_none().then_some("font-bold")
+                        )}
+                        onclick={open_public}
+                    >
+                        {"Users"}
+                    </div>
                     {
                         self.users.clone().iter().map(|u| {
+                            let unread = self.unread.get(&u.name).copied().unwrap_or(0);
+                            let is_active = self.active_conversation.as_deref() == Some(u.name.as_str());
+                            let peer = u.name.clone();
+                            let select_peer = peer.clone();
+                            let select = ctx.link().callback(move |_| Msg::SelectConversation(Some(select_peer.clone())));
+                            let call_peer = peer.clone();
+                            let call = ctx.link().callback(move |e: MouseEvent| {
+                                e.stop_propagation();
+                                Msg::Call(call_peer.clone())
+                            });
                             html!{
-                                <div class="flex m-3 bg-white rounded-lg p-2">
+                                <div
+                                    class={classes!(
+                                        "flex", "m-3", "bg-white", "rounded-lg", "p-2", "cursor-pointer",
+                                        is_active.then_some("ring-2"), is_active.then_some("ring-blue-400")
+                                    )}
+                                    onclick={select}
+                                >
                                     <div>
                                         <img class="w-12 h-12 rounded-full" src={u.avatar.clone()} alt="avatar"/>
                                     </div>
                                     <div class="flex-grow p-3">
                                         <div class="flex text-xs justify-between">
                                             <div>{u.name.clone()}</div>
+                                            {
+                                                if unread > 0 {
+                                                    html! { <div class="bg-blue-600 text-white rounded-full px-2">{unread}</div> }
+                                                } else {
+                                                    html! {}
+                                                }
+                                            }
                                         </div>
                                         <div class="text-xs text-gray-400">
                                             {"Hi there!"}
                                         </div>
                                     </div>
+                                    <button onclick={call} title="Call" class="text-lg">{"📞"}</button>
                                 </div>
                             }
                         }).collect::<Html>()
                     }
                 </div>
                 <div class="grow h-screen flex flex-col">
-                    <div class="w-full h-14 border-b-2 border-gray-300"><div class="text-xl p-3">{"💬 Chat!"}</div></div>
+                    <div class="w-full h-14 border-b-2 border-gray-300">
+                        <div class="text-xl p-3">
+                            {
+                                match &self.active_conversation {
+                                    Some(peer) => format!("🤫 Whisper: {peer}"),
+                                    None => "💬 Chat!".to_string(),
+                                }
+                            }
+                        </div>
+                    </div>
+                    {
+                        if let Some((classes, label)) = status_banner {
+                            html! {
+                                <div class={format!("w-full text-xs text-center py-1 {}", classes)}>
+                                    {label}
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if let Some(from) = &self.incoming_call {
+                            let accept = ctx.link().callback(|_| Msg::AcceptCall);
+                            let decline = ctx.link().callback(|_| Msg::DeclineCall);
+                            html! {
+                                <div class="w-full bg-green-100 text-green-900 text-sm p-2 flex justify-between items-center">
+                                    <div>{format!("📞 Incoming call from {from}")}</div>
+                                    <div>
+                                        <button onclick={accept} class="bg-green-600 text-white rounded-full px-3 py-1 mr-2">{"Accept"}</button>
+                                        <button onclick={decline} class="bg-red-600 text-white rounded-full px-3 py-1">{"Decline"}</button>
+                                    </div>
+                                </div>
+                            }
+                        } else if let Some(peer) = &self.active_call {
+                            let hang_up = ctx.link().callback(|_| Msg::HangUp);
+                            html! {
+                                <div class="w-full bg-blue-100 text-blue-900 text-sm p-2 flex justify-between items-center">
+                                    <div>{format!("📞 On call with {peer}")}</div>
+                                    <button onclick={hang_up} class="bg-red-600 text-white rounded-full px-3 py-1">{"Hang up"}</button>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
                     <div class="w-full grow overflow-auto border-b-2 border-gray-300">
                         {
-                            self.messages.iter().map(|m| {
+                            active_messages.iter().map(|m| {
                                 // Find user or create a default one if not found
                                 let user_opt = self.users.iter().find(|u| u.name == m.from);
                                 let user = match user_opt {
@@ -221,15 +480,7 @@ impl Component for Chat {
                                                 </div>
                                             </div>
                                             <div class="text-xs text-gray-500 mt-1">
-                                                {
-                                                    if m.message.ends_with(".gif") {
-                                                        html! {
-                                                            <img class="mt-3" src={m.message.clone()}/>
-                                                        }
-                                                    } else {
-                                                        html! { {m.message.clone()} }
-                                                    }
-                                                }
+                                                {render_content(&m.message)}
                                             </div>
                                         </div>
                                     </div>
@@ -237,7 +488,33 @@ impl Component for Chat {
                             }).collect::<Html>()
                         }
                     </div>
+                    {
+                        match &self.upload_status {
+                            Some(UploadStatus::Uploading) => html! {
+                                <div class="w-full text-xs text-center py-1 bg-gray-100 text-gray-500">{"Uploading attachment…"}</div>
+                            },
+                            Some(UploadStatus::Failed(reason)) => html! {
+                                <div class="w-full text-xs text-center py-1 bg-red-100 text-red-800">{format!("Upload failed: {reason}")}</div>
+                            },
+                            None => html! {},
+                        }
+                    }
                     <div class="w-full h-14 flex px-3 items-center">
+                        <input
+                            ref={self.file_input.clone()}
+                            onchange={ctx.link().callback(|e: Event| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                let file = input.files().and_then(|files| files.get(0));
+                                match file {
+                                    Some(file) => Msg::FileSelected(file),
+                                    None => Msg::AttachmentUploaded(Err("no file selected".to_string())),
+                                }
+                            })}
+                            type="file"
+                            id="attachment-input"
+                            class="hidden"
+                        />
+                        <label for="attachment-input" class="p-3 cursor-pointer text-xl" title="Attach a file">{"📎"}</label>
                         <input ref={self.chat_input.clone()} type="text" placeholder="Message" class="block w-full py-2 pl-4 mx-3 bg-gray-100 rounded-full outline-none focus:text-gray-700" name="message" required=true />
                         <button onclick={submit} class="p-3 shadow-sm bg-blue-600 w-10 h-10 rounded-full flex justify-center items-center color-white">
                             <svg viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="fill-white">
@@ -249,4 +526,39 @@ impl Component for Chat {
             </div>
         }
     }
+}
+
+impl Chat {
+    /// Wraps `content` in a `MessageData` envelope addressed at the active
+    /// conversation, sending it immediately or queuing it if disconnected.
+    fn send_content(&mut self, content: MessageContent) {
+        let js_time = js_sys::Date::new_0().to_locale_time_string("id-ID");
+        let current_time = js_time.as_string().unwrap_or_default();
+
+        let message_data = serde_json::json!({
+            "message": content,
+            "timestamp": current_time,
+            "to": self.active_conversation,
+        });
+
+        let message_type = if self.active_conversation.is_some() {
+            MsgTypes::Whisper
+        } else {
+            MsgTypes::Message
+        };
+        let message = WebSocketMessage {
+            message_type,
+            data: Some(serde_json::to_string(&message_data).unwrap()),
+            data_array: None,
+            codec: None,
+        };
+
+        if self.status == Status::Connected {
+            if let Err(e) = self.wss.tx.clone().try_send(message) {
+                log::error!("Error sending to channel: {:?}", e);
+            }
+        } else {
+            self.pending.push(message);
+        }
+    }
 }
\ No newline at end of file
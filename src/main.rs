@@ -0,0 +1,32 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+mod components;
+mod markdown;
+mod services;
+
+use components::chat::Chat;
+use yew::prelude::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub username: Rc<RefCell<String>>,
+}
+
+#[function_component(App)]
+fn app() -> Html {
+    let username = use_state(|| Rc::new(RefCell::new(String::from("anon"))));
+    let user = User {
+        username: (*username).clone(),
+    };
+
+    html! {
+        <ContextProvider<User> context={user}>
+            <Chat />
+        </ContextProvider<User>>
+    }
+}
+
+fn main() {
+    yew::Renderer::<App>::new().render();
+}
@@ -0,0 +1,146 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use yew::{AttrValue, Html};
+
+const IMAGE_EXTENSIONS: [&str; 5] = [".gif", ".png", ".jpg", ".jpeg", ".webp"];
+
+fn is_image_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    IMAGE_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
+fn is_bare_url(token: &str) -> bool {
+    token.starts_with("http://") || token.starts_with("https://")
+}
+
+/// Wraps bare `http(s)://` tokens in Markdown link syntax so pulldown-cmark
+/// renders them as (potentially image) links instead of plain text.
+fn autolink(raw: &str) -> String {
+    raw.split(' ')
+        .map(|token| {
+            if is_bare_url(token) {
+                format!("[{token}]({token})")
+            } else {
+                token.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Rewrites `[text](url)` links whose target looks like an image into
+/// `![text](url)` image events, so a pasted image/gif URL still renders
+/// inline instead of as a clickable link.
+fn rewrite_image_links(parser: Parser) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut in_image_link = false;
+    for event in parser {
+        match event {
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if is_image_url(&dest_url) => {
+                in_image_link = true;
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::End(TagEnd::Link) if in_image_link => {
+                in_image_link = false;
+                events.push(Event::End(TagEnd::Image));
+            }
+            other => events.push(other),
+        }
+    }
+    events
+}
+
+/// Strips anything not on the allow-list (scripts, event handlers, etc.) so
+/// a peer's message can't inject arbitrary HTML into the page.
+fn sanitize(unsafe_html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(["img"])
+        .add_tag_attributes("img", ["src", "alt", "title"])
+        .clean(unsafe_html)
+        .to_string()
+}
+
+/// Renders a chat message body as sanitized HTML: bold/italics/inline code,
+/// fenced code blocks, links, auto-linked bare URLs, and inline images for
+/// links that point at an image.
+pub fn render(raw: &str) -> Html {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+
+    let linked = autolink(raw);
+    let parser = Parser::new_ext(&linked, options);
+    let events = rewrite_image_links(parser);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events.into_iter());
+
+    let safe_html = sanitize(&unsafe_html);
+    Html::from_html_unchecked(AttrValue::from(safe_html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_image_url_matches_known_extensions() {
+        assert!(is_image_url("https://example.com/cat.png"));
+        assert!(is_image_url("https://example.com/CAT.JPG"));
+        assert!(!is_image_url("https://example.com/page.html"));
+    }
+
+    #[test]
+    fn autolink_wraps_bare_urls_only() {
+        assert_eq!(
+            autolink("see https://example.com/cat.png for cats"),
+            "see [https://example.com/cat.png](https://example.com/cat.png) for cats"
+        );
+        assert_eq!(autolink("no urls here"), "no urls here");
+    }
+
+    #[test]
+    fn sanitize_strips_script_tags() {
+        let cleaned = sanitize("hi <script>alert(1)</script> there");
+        assert!(!cleaned.contains("<script"));
+        assert!(!cleaned.contains("alert(1)"));
+    }
+
+    #[test]
+    fn sanitize_strips_event_handler_attributes() {
+        let cleaned = sanitize(r#"<img src="x.png" onerror="alert(1)">"#);
+        assert!(!cleaned.contains("onerror"));
+    }
+
+    #[test]
+    fn sanitize_strips_javascript_urls() {
+        let cleaned = sanitize(r#"<a href="javascript:alert(1)">click</a>"#);
+        assert!(!cleaned.contains("javascript:"));
+    }
+
+    #[test]
+    fn sanitize_allows_image_tags() {
+        let cleaned = sanitize(r#"<img src="https://example.com/cat.png" alt="cat">"#);
+        assert!(cleaned.contains("<img"));
+        assert!(cleaned.contains("src=\"https://example.com/cat.png\""));
+    }
+
+    #[test]
+    fn bare_image_url_autolinks_into_an_image_tag() {
+        let parser = Parser::new_ext(&autolink("https://example.com/cat.png"), Options::empty());
+        let events = rewrite_image_links(parser);
+        let mut html_out = String::new();
+        html::push_html(&mut html_out, events.into_iter());
+        assert!(html_out.contains("<img"));
+        assert!(html_out.contains("src=\"https://example.com/cat.png\""));
+    }
+}
@@ -0,0 +1,422 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::channel::mpsc::Sender;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    HtmlAudioElement, MediaStream, MediaStreamConstraints, RtcIceCandidate, RtcIceCandidateInit,
+    RtcIceConnectionState, RtcPeerConnection, RtcPeerConnectionIceEvent,
+    RtcSdpType, RtcSessionDescriptionInit, RtcTrackEvent,
+};
+use yew::Callback;
+
+use super::websocket::{MsgTypes, WebSocketMessage};
+
+const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+/// Offer/answer/ICE-candidate signaling relayed through the existing
+/// WebSocket, addressed at a single peer.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SignalPayload {
+    Offer {
+        from: String,
+        to: String,
+        sdp: String,
+    },
+    Answer {
+        from: String,
+        to: String,
+        sdp: String,
+    },
+    IceCandidate {
+        from: String,
+        to: String,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    /// Sent when a call is declined or hung up, so the other side doesn't
+    /// get stuck believing the call is still live.
+    Bye {
+        from: String,
+        to: String,
+    },
+}
+
+/// Call lifecycle surfaced to `Chat` so it can render the incoming-call
+/// prompt and in-call indicator.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallEvent {
+    Ringing { from: String },
+    Connected { peer: String },
+    Ended,
+}
+
+struct State {
+    username: String,
+    peer_connection: Option<RtcPeerConnection>,
+    /// Offer we haven't accepted/declined yet.
+    pending_offer: Option<(String, String)>,
+    active_peer: Option<String>,
+    /// Guards against emitting `CallEvent::Connected` more than once per call.
+    connected_emitted: bool,
+}
+
+pub struct CallService {
+    tx: Sender<WebSocketMessage>,
+    on_event: Callback<CallEvent>,
+    state: Rc<RefCell<State>>,
+}
+
+fn send_signal(tx: &Sender<WebSocketMessage>, payload: &SignalPayload) {
+    let Ok(data) = serde_json::to_string(payload) else {
+        return;
+    };
+    let message = WebSocketMessage {
+        message_type: MsgTypes::Signal,
+        data: Some(data),
+        data_array: None,
+        codec: None,
+    };
+    let _ = tx.clone().try_send(message);
+}
+
+/// Attaches an incoming remote audio stream to a hidden `<audio>` element
+/// appended to the document body so it's actually audible.
+fn play_remote_stream(stream: &MediaStream) -> Option<()> {
+    let document = web_sys::window()?.document()?;
+    let audio: HtmlAudioElement = document.create_element("audio").ok()?.unchecked_into();
+    audio.set_autoplay(true);
+    let _ = audio.set_attribute("hidden", "true");
+    audio.set_src_object(Some(stream));
+    document.body()?.append_child(&audio).ok()?;
+    let _ = audio.play();
+    Some(())
+}
+
+fn new_peer_connection(
+    state: Rc<RefCell<State>>,
+    tx: Sender<WebSocketMessage>,
+    on_event: Callback<CallEvent>,
+    peer: String,
+) -> Result<RtcPeerConnection, JsValue> {
+    let mut config = web_sys::RtcConfiguration::new();
+    let ice_servers = js_sys::Array::new();
+    let server = js_sys::Object::new();
+    js_sys::Reflect::set(&server, &"urls".into(), &STUN_SERVER.into())?;
+    ice_servers.push(&server);
+    config.ice_servers(&ice_servers);
+
+    let pc = RtcPeerConnection::new_with_configuration(&config)?;
+    // Reset the one-shot `Connected` guard for this fresh call.
+    state.borrow_mut().connected_emitted = false;
+
+    let ice_tx = tx.clone();
+    let ice_state = state.clone();
+    let ice_peer = peer.clone();
+    let onicecandidate = Closure::wrap(Box::new(move |e: RtcPeerConnectionIceEvent| {
+        if let Some(candidate) = e.candidate() {
+            let username = ice_state.borrow().username.clone();
+            send_signal(
+                &ice_tx,
+                &SignalPayload::IceCandidate {
+                    from: username,
+                    to: ice_peer.clone(),
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                },
+            );
+        }
+    }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
+    pc.set_onicecandidate(Some(onicecandidate.as_ref().unchecked_ref()));
+    onicecandidate.forget();
+
+    // Only report the call as actually connected once ICE has a live path,
+    // not as soon as we've merely sent an offer/answer.
+    let state_change_pc = pc.clone();
+    let state_change_state = state.clone();
+    let state_change_event = on_event.clone();
+    let state_change_peer = peer.clone();
+    let oniceconnectionstatechange = Closure::wrap(Box::new(move |_: JsValue| {
+        if !connection_is_live(state_change_pc.ice_connection_state()) {
+            return;
+        }
+        let mut s = state_change_state.borrow_mut();
+        if s.connected_emitted {
+            return;
+        }
+        s.connected_emitted = true;
+        drop(s);
+        state_change_event.emit(CallEvent::Connected {
+            peer: state_change_peer.clone(),
+        });
+    }) as Box<dyn FnMut(JsValue)>);
+    pc.set_oniceconnectionstatechange(Some(oniceconnectionstatechange.as_ref().unchecked_ref()));
+    oniceconnectionstatechange.forget();
+
+    let ontrack = Closure::wrap(Box::new(move |e: RtcTrackEvent| {
+        let streams = e.streams();
+        if streams.length() == 0 {
+            return;
+        }
+        let stream: MediaStream = streams.get(0).unchecked_into();
+        if play_remote_stream(&stream).is_none() {
+            log::error!("Failed to play remote audio stream");
+        }
+    }) as Box<dyn FnMut(RtcTrackEvent)>);
+    pc.set_ontrack(Some(ontrack.as_ref().unchecked_ref()));
+    ontrack.forget();
+
+    Ok(pc)
+}
+
+async fn attach_microphone(pc: &RtcPeerConnection) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let media_devices = window.navigator().media_devices()?;
+    let mut constraints = MediaStreamConstraints::new();
+    constraints.audio(&JsValue::TRUE);
+    let promise = media_devices.get_user_media_with_constraints(&constraints)?;
+    let stream: MediaStream = JsFuture::from(promise).await?.unchecked_into();
+    for track in stream.get_tracks().iter() {
+        pc.add_track_0(&track.unchecked_into(), &stream);
+    }
+    Ok(())
+}
+
+impl CallService {
+    pub fn new(username: String, tx: Sender<WebSocketMessage>, on_event: Callback<CallEvent>) -> Self {
+        Self {
+            tx,
+            on_event,
+            state: Rc::new(RefCell::new(State {
+                username,
+                peer_connection: None,
+                pending_offer: None,
+                active_peer: None,
+                connected_emitted: false,
+            })),
+        }
+    }
+
+    /// Places an outgoing call: creates the peer connection, grabs the mic,
+    /// and sends an SDP offer to `peer`.
+    pub fn call(&self, peer: String) {
+        let tx = self.tx.clone();
+        let state = self.state.clone();
+        let on_event = self.on_event.clone();
+
+        spawn_local(async move {
+            let pc = match new_peer_connection(state.clone(), tx.clone(), on_event.clone(), peer.clone()) {
+                Ok(pc) => pc,
+                Err(e) => {
+                    log::error!("Failed to create RtcPeerConnection: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = attach_microphone(&pc).await {
+                log::error!("Failed to access microphone: {:?}", e);
+                return;
+            }
+
+            let offer = match JsFuture::from(pc.create_offer()).await {
+                Ok(offer) => offer,
+                Err(e) => {
+                    log::error!("Failed to create SDP offer: {:?}", e);
+                    return;
+                }
+            };
+            let mut desc = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+            let sdp = js_sys::Reflect::get(&offer, &"sdp".into())
+                .ok()
+                .and_then(|v| v.as_string())
+                .unwrap_or_default();
+            desc.sdp(&sdp);
+            if let Err(e) = JsFuture::from(pc.set_local_description(&desc)).await {
+                log::error!("Failed to set local description: {:?}", e);
+                return;
+            }
+
+            let username = state.borrow().username.clone();
+            send_signal(
+                &tx,
+                &SignalPayload::Offer {
+                    from: username,
+                    to: peer.clone(),
+                    sdp,
+                },
+            );
+
+            let mut s = state.borrow_mut();
+            s.peer_connection = Some(pc);
+            s.active_peer = Some(peer);
+        });
+    }
+
+    /// Handles an inbound `Signal` envelope, routing it to the right stage
+    /// of the offer/answer/ICE handshake.
+    pub fn handle_signal(&self, payload: SignalPayload) {
+        match payload {
+            SignalPayload::Offer { from, sdp, .. } => {
+                self.state.borrow_mut().pending_offer = Some((from.clone(), sdp));
+                self.on_event.emit(CallEvent::Ringing { from });
+            }
+            SignalPayload::Answer { sdp, .. } => {
+                let state = self.state.clone();
+                spawn_local(async move {
+                    let Some(pc) = state.borrow().peer_connection.clone() else {
+                        return;
+                    };
+                    let mut desc = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                    desc.sdp(&sdp);
+                    if let Err(e) = JsFuture::from(pc.set_remote_description(&desc)).await {
+                        log::error!("Failed to set remote description: {:?}", e);
+                    }
+                });
+            }
+            SignalPayload::IceCandidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+                ..
+            } => {
+                let state = self.state.clone();
+                spawn_local(async move {
+                    let Some(pc) = state.borrow().peer_connection.clone() else {
+                        return;
+                    };
+                    let mut init = RtcIceCandidateInit::new(&candidate);
+                    init.sdp_mid(sdp_mid.as_deref());
+                    init.sdp_m_line_index(sdp_m_line_index);
+                    match RtcIceCandidate::new(&init) {
+                        Ok(candidate) => {
+                            if let Err(e) =
+                                JsFuture::from(pc.add_ice_candidate_with_rtc_ice_candidate(&candidate)).await
+                            {
+                                log::error!("Failed to add ICE candidate: {:?}", e);
+                            }
+                        }
+                        Err(e) => log::error!("Invalid ICE candidate: {:?}", e),
+                    }
+                });
+            }
+            SignalPayload::Bye { .. } => {
+                let mut s = self.state.borrow_mut();
+                if let Some(pc) = s.peer_connection.take() {
+                    pc.close();
+                }
+                s.active_peer = None;
+                s.pending_offer = None;
+                drop(s);
+                self.on_event.emit(CallEvent::Ended);
+            }
+        }
+    }
+
+    /// Accepts the currently pending incoming offer.
+    pub fn accept_incoming(&self) {
+        let Some((from, sdp)) = self.state.borrow_mut().pending_offer.take() else {
+            return;
+        };
+        let tx = self.tx.clone();
+        let state = self.state.clone();
+        let on_event = self.on_event.clone();
+
+        spawn_local(async move {
+            let pc = match new_peer_connection(state.clone(), tx.clone(), on_event.clone(), from.clone()) {
+                Ok(pc) => pc,
+                Err(e) => {
+                    log::error!("Failed to create RtcPeerConnection: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = attach_microphone(&pc).await {
+                log::error!("Failed to access microphone: {:?}", e);
+                return;
+            }
+
+            let mut remote_desc = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+            remote_desc.sdp(&sdp);
+            if let Err(e) = JsFuture::from(pc.set_remote_description(&remote_desc)).await {
+                log::error!("Failed to set remote description: {:?}", e);
+                return;
+            }
+
+            let answer = match JsFuture::from(pc.create_answer()).await {
+                Ok(answer) => answer,
+                Err(e) => {
+                    log::error!("Failed to create SDP answer: {:?}", e);
+                    return;
+                }
+            };
+            let answer_sdp = js_sys::Reflect::get(&answer, &"sdp".into())
+                .ok()
+                .and_then(|v| v.as_string())
+                .unwrap_or_default();
+            let mut local_desc = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+            local_desc.sdp(&answer_sdp);
+            if let Err(e) = JsFuture::from(pc.set_local_description(&local_desc)).await {
+                log::error!("Failed to set local description: {:?}", e);
+                return;
+            }
+
+            let username = state.borrow().username.clone();
+            send_signal(
+                &tx,
+                &SignalPayload::Answer {
+                    from: username,
+                    to: from.clone(),
+                    sdp: answer_sdp,
+                },
+            );
+
+            let mut s = state.borrow_mut();
+            s.peer_connection = Some(pc);
+            s.active_peer = Some(from);
+        });
+    }
+
+    /// Declines the currently pending incoming offer without answering, and
+    /// tells the caller so they aren't left waiting on a call nobody picked up.
+    pub fn decline_incoming(&self) {
+        let Some((from, _sdp)) = self.state.borrow_mut().pending_offer.take() else {
+            return;
+        };
+        let username = self.state.borrow().username.clone();
+        send_signal(
+            &self.tx,
+            &SignalPayload::Bye {
+                from: username,
+                to: from,
+            },
+        );
+        self.on_event.emit(CallEvent::Ended);
+    }
+
+    pub fn hang_up(&self) {
+        let mut state = self.state.borrow_mut();
+        let peer = state.active_peer.take();
+        if let Some(pc) = state.peer_connection.take() {
+            pc.close();
+        }
+        state.pending_offer = None;
+        let username = state.username.clone();
+        drop(state);
+
+        if let Some(peer) = peer {
+            send_signal(&self.tx, &SignalPayload::Bye { from: username, to: peer });
+        }
+        self.on_event.emit(CallEvent::Ended);
+    }
+}
+
+fn connection_is_live(state: RtcIceConnectionState) -> bool {
+    matches!(
+        state,
+        RtcIceConnectionState::Connected | RtcIceConnectionState::Completed
+    )
+}
@@ -0,0 +1,4 @@
+pub mod call;
+pub mod event_bus;
+pub mod upload;
+pub mod websocket;
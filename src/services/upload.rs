@@ -0,0 +1,73 @@
+use gloo_net::http::Request;
+use serde::{Deserialize, Serialize};
+use web_sys::{File, FormData};
+
+/// Where selected files are uploaded before the resulting URL is shared in
+/// the chat; point this at whatever static/object storage backs the server.
+const UPLOAD_URL: &str = "/upload";
+
+/// Reject attachments above this size before even starting the upload.
+pub const MAX_ATTACHMENT_BYTES: f64 = 10.0 * 1024.0 * 1024.0;
+
+/// A file that has finished uploading and can now be shared as a message.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Attachment {
+    pub url: String,
+    pub mime: String,
+    pub filename: String,
+}
+
+#[derive(Deserialize)]
+struct UploadResponse {
+    url: String,
+}
+
+/// Uploads `file` to [`UPLOAD_URL`] and returns where it ended up. The MIME
+/// type is guessed from the filename when the browser didn't supply one
+/// (e.g. drag-and-drop of an extension-less file from some OSes).
+pub async fn upload(file: File) -> Result<Attachment, String> {
+    if file.size() > MAX_ATTACHMENT_BYTES {
+        return Err(format!(
+            "{} is larger than the {}MB limit",
+            file.name(),
+            (MAX_ATTACHMENT_BYTES / 1024.0 / 1024.0) as u64
+        ));
+    }
+
+    let mime = {
+        let native = file.type_();
+        if native.is_empty() {
+            mime_guess::from_path(file.name())
+                .first_or_octet_stream()
+                .to_string()
+        } else {
+            native
+        }
+    };
+
+    let form = FormData::new().map_err(|_| "failed to build form data".to_string())?;
+    form.append_with_blob_and_filename("file", &file, &file.name())
+        .map_err(|_| "failed to attach file".to_string())?;
+
+    let response = Request::post(UPLOAD_URL)
+        .body(form)
+        .map_err(|e| format!("failed to build upload request: {e}"))?
+        .send()
+        .await
+        .map_err(|e| format!("upload failed: {e}"))?;
+
+    if !response.ok() {
+        return Err(format!("upload failed with status {}", response.status()));
+    }
+
+    let body: UploadResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("invalid upload response: {e}"))?;
+
+    Ok(Attachment {
+        url: body.url,
+        mime,
+        filename: file.name(),
+    })
+}
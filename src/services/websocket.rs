@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::channel::mpsc::{channel, Sender};
+use futures::StreamExt;
+use gloo_timers::callback::Timeout;
+use js_sys::Uint8Array;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+use yew::Callback;
+use yew_agent::{Dispatched, Dispatcher};
+
+use super::event_bus::{EventBus, Request as EventBusRequest};
+
+const WS_URL: &str = "ws://127.0.0.1:8081/ws";
+const INITIAL_BACKOFF_MS: u32 = 500;
+const MAX_BACKOFF_MS: u32 = 30_000;
+const UNSUPPORTED_CODEC: &str = "unsupported-codec";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MsgTypes {
+    Users,
+    Register,
+    Message,
+    Whisper,
+    Signal,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    pub message_type: MsgTypes,
+    pub data_array: Option<Vec<String>>,
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+}
+
+/// Connection lifecycle, surfaced to subscribers so the UI can explain why
+/// messages aren't flowing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Disconnected,
+}
+
+/// Wire format used for outgoing/incoming frames. The client asks for CBOR
+/// during registration but stays on JSON until the server positively
+/// confirms it understands CBOR; this keeps JSON as the default for
+/// backward compatibility with servers that don't recognize the `codec`
+/// field at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Codec {
+    Json,
+    Cbor,
+}
+
+enum WsAction {
+    Connect,
+    SendData(WebSocketMessage),
+    Disconnect,
+    Lost,
+}
+
+struct Shared {
+    socket: Option<WebSocket>,
+    username: String,
+    backoff_ms: u32,
+    codec: Codec,
+    on_status: Callback<Status>,
+}
+
+fn send_message(shared: &Rc<RefCell<Shared>>, message: &WebSocketMessage) {
+    let (socket, codec) = {
+        let s = shared.borrow();
+        (s.socket.clone(), s.codec)
+    };
+    let Some(socket) = socket else { return };
+    match codec {
+        Codec::Json => {
+            if let Ok(json) = serde_json::to_string(message) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+        Codec::Cbor => match serde_cbor::to_vec(message) {
+            Ok(bytes) => {
+                let _ = socket.send_with_u8_array(&bytes);
+            }
+            Err(e) => log::error!("Failed to encode CBOR frame: {:?}", e),
+        },
+    }
+}
+
+fn register(shared: &Rc<RefCell<Shared>>) {
+    let username = shared.borrow().username.clone();
+    // The handshake itself always travels as JSON text so the server can
+    // read it before any codec has been negotiated. `codec` is a request,
+    // not a commitment: we stay on JSON until the server positively
+    // confirms it understood CBOR (see `handle_incoming`), so a legacy
+    // server that silently ignores the field is never sent frames it can't
+    // parse.
+    let message = WebSocketMessage {
+        message_type: MsgTypes::Register,
+        data: Some(username),
+        data_array: None,
+        codec: Some("cbor".to_string()),
+    };
+    if let Some(socket) = shared.borrow().socket.clone() {
+        if let Ok(json) = serde_json::to_string(&message) {
+            let _ = socket.send_with_str(&json);
+        }
+    }
+}
+
+fn handle_incoming(shared: &Rc<RefCell<Shared>>, message: WebSocketMessage) {
+    if matches!(message.message_type, MsgTypes::Error)
+        && message.data.as_deref() == Some(UNSUPPORTED_CODEC)
+    {
+        log::debug!("server does not support CBOR, staying on JSON");
+        shared.borrow_mut().codec = Codec::Json;
+        return;
+    }
+    if message.codec.as_deref() == Some("cbor") {
+        log::debug!("server confirmed CBOR support");
+        shared.borrow_mut().codec = Codec::Cbor;
+    }
+    if let Ok(json) = serde_json::to_string(&message) {
+        EventBus::dispatcher().send(EventBusRequest::EventBusMsg(json));
+    }
+}
+
+fn apply(shared: &Rc<RefCell<Shared>>, action: WsAction) {
+    match action {
+        WsAction::Connect => connect(shared.clone()),
+        WsAction::SendData(message) => send_message(shared, &message),
+        WsAction::Disconnect => {
+            if let Some(socket) = shared.borrow_mut().socket.take() {
+                let _ = socket.close();
+            }
+            shared.borrow().on_status.emit(Status::Disconnected);
+        }
+        WsAction::Lost => schedule_reconnect(shared.clone()),
+    }
+}
+
+fn connect(shared: Rc<RefCell<Shared>>) {
+    shared.borrow().on_status.emit(Status::Connecting);
+
+    let ws = match WebSocket::new(WS_URL) {
+        Ok(ws) => ws,
+        Err(_) => {
+            apply(&shared, WsAction::Lost);
+            return;
+        }
+    };
+    ws.set_binary_type(BinaryType::Arraybuffer);
+
+    let onopen_shared = shared.clone();
+    let onopen = Closure::wrap(Box::new(move |_: JsValue| {
+        {
+            let mut s = onopen_shared.borrow_mut();
+            s.backoff_ms = INITIAL_BACKOFF_MS;
+            // Each connection renegotiates the codec from scratch.
+            s.codec = Codec::Json;
+        }
+        onopen_shared.borrow().on_status.emit(Status::Connected);
+        register(&onopen_shared);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    let onmessage_shared = shared.clone();
+    let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+        if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
+            match serde_json::from_str::<WebSocketMessage>(&String::from(txt)) {
+                Ok(message) => handle_incoming(&onmessage_shared, message),
+                Err(e) => log::error!("Error parsing JSON frame: {:?}", e),
+            }
+        } else if let Ok(buf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+            let bytes = Uint8Array::new(&buf).to_vec();
+            match serde_cbor::from_slice::<WebSocketMessage>(&bytes) {
+                Ok(message) => handle_incoming(&onmessage_shared, message),
+                Err(e) => log::error!("Error parsing CBOR frame: {:?}", e),
+            }
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    let onclose_shared = shared.clone();
+    let onclose = Closure::wrap(Box::new(move |_: JsValue| {
+        onclose_shared.borrow_mut().socket = None;
+        apply(&onclose_shared, WsAction::Lost);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+
+    let onerror_shared = shared.clone();
+    let onerror = Closure::wrap(Box::new(move |_: JsValue| {
+        onerror_shared.borrow_mut().socket = None;
+        apply(&onerror_shared, WsAction::Lost);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+    onerror.forget();
+
+    shared.borrow_mut().socket = Some(ws);
+}
+
+fn schedule_reconnect(shared: Rc<RefCell<Shared>>) {
+    shared.borrow().on_status.emit(Status::Reconnecting);
+    let delay = shared.borrow().backoff_ms;
+    let next_shared = shared.clone();
+    Timeout::new(delay, move || {
+        {
+            let mut s = next_shared.borrow_mut();
+            s.backoff_ms = (s.backoff_ms * 2).min(MAX_BACKOFF_MS);
+        }
+        connect(next_shared);
+    })
+    .forget();
+}
+
+pub struct WebsocketService {
+    pub tx: Sender<WebSocketMessage>,
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl WebsocketService {
+    pub fn new(username: String, on_status: Callback<Status>) -> Self {
+        let shared = Rc::new(RefCell::new(Shared {
+            socket: None,
+            username,
+            backoff_ms: INITIAL_BACKOFF_MS,
+            codec: Codec::Json,
+            on_status,
+        }));
+
+        let (tx, mut rx) = channel::<WebSocketMessage>(1000);
+
+        apply(&shared, WsAction::Connect);
+
+        let out_shared = shared.clone();
+        spawn_local(async move {
+            while let Some(message) = rx.next().await {
+                apply(&out_shared, WsAction::SendData(message));
+            }
+        });
+
+        Self { tx, shared }
+    }
+
+    pub fn disconnect(&self) {
+        apply(&self.shared, WsAction::Disconnect);
+    }
+}